@@ -6,9 +6,10 @@ use std::path::PathBuf;
 use crate::indexer::Chunk;
 
 #[derive(Serialize)]
-struct BridgeRequest {
-    chunks: Vec<Chunk>,
-    query: String,
+#[serde(tag = "command", rename_all = "snake_case")]
+enum BridgeRequest {
+    Assemble { chunks: Vec<Chunk>, query: String },
+    Embed { chunks: Vec<Chunk> },
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -18,6 +19,11 @@ pub struct CompilerOutput {
     pub filename: String,
 }
 
+#[derive(Deserialize, Debug)]
+struct EmbedOutput {
+    vectors: Vec<Vec<f32>>,
+}
+
 pub struct Assembler {
     python_path: String,
     bridge_script: PathBuf,
@@ -61,6 +67,32 @@ impl Assembler {
     }
 
     pub fn generate_glue_code(&self, chunks: Vec<Chunk>, query: String) -> Result<CompilerOutput> {
+        let request = BridgeRequest::Assemble { chunks, query };
+        self.run_bridge(&request)
+    }
+
+    /// Embed a batch of chunks via the bridge's `embed` command, returning one
+    /// vector per chunk in the same order. Used both to populate the index at
+    /// indexing time and to embed the user's query at search time.
+    pub fn embed_chunks(&self, chunks: Vec<Chunk>) -> Result<Vec<Vec<f32>>> {
+        let request = BridgeRequest::Embed { chunks };
+        let output: EmbedOutput = self.run_bridge(&request)?;
+        Ok(output.vectors)
+    }
+
+    pub fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        let pseudo_chunk = Chunk {
+            source: query.to_string(),
+            path: String::new(),
+            filename: String::new(),
+            func_name: "__query__".to_string(),
+            language: String::new(),
+        };
+        let mut vectors = self.embed_chunks(vec![pseudo_chunk])?;
+        vectors.pop().ok_or_else(|| anyhow::anyhow!("Bridge returned no embedding for query"))
+    }
+
+    fn run_bridge<T: serde::de::DeserializeOwned>(&self, request: &BridgeRequest) -> Result<T> {
         if !self.bridge_script.exists() {
             anyhow::bail!(
                 "Bridge script not found. Please ensure '{}' exists.\n\
@@ -77,25 +109,21 @@ impl Assembler {
             .spawn()
             .context(format!("Failed to spawn python ({})", self.python_path))?;
 
-        let request = BridgeRequest { chunks, query };
-        let json_input = serde_json::to_string(&request)?;
+        let json_input = serde_json::to_string(request)?;
 
         if let Some(mut stdin) = child.stdin.take() {
             stdin.write_all(json_input.as_bytes())?;
         }
 
         let output = child.wait_with_output()?;
-        
+
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             anyhow::bail!("Python bridge failed: {}", stderr);
         }
 
         let output_str = String::from_utf8(output.stdout)?;
-        let result: CompilerOutput = serde_json::from_str(&output_str)
-            .context("Failed to parse bridge output")?;
-
-        Ok(result)
+        serde_json::from_str(&output_str).context("Failed to parse bridge output")
     }
 }
 
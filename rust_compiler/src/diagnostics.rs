@@ -0,0 +1,75 @@
+//! Turns a raw Python traceback from a failed run into a located,
+//! annotate-snippets-style rendering: the offending line plus a line or two
+//! of context, a caret under the relevant span, and the error text as the
+//! label underneath.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+/// Pull the innermost "File ..., line N" frame and the trailing exception
+/// message out of a Python traceback. Returns `None` if the stderr doesn't
+/// look like a traceback at all.
+pub fn parse_traceback(stderr: &str) -> Option<Diagnostic> {
+    let mut file = None;
+    let mut line = None;
+
+    for raw_line in stderr.lines() {
+        let trimmed = raw_line.trim();
+        let Some(rest) = trimmed.strip_prefix("File \"") else { continue };
+        let Some(end_quote) = rest.find('"') else { continue };
+        let candidate_file = &rest[..end_quote];
+
+        let Some(line_part) = rest[end_quote..].split("line ").nth(1) else { continue };
+        let digits: String = line_part.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let Ok(parsed_line) = digits.parse::<usize>() else { continue };
+
+        // Traceback frames are printed outermost-first, so the last match is
+        // the innermost frame - usually the one worth pointing at.
+        file = Some(candidate_file.to_string());
+        line = Some(parsed_line);
+    }
+
+    let message = stderr.lines().rev().find(|l| !l.trim().is_empty())?.trim().to_string();
+
+    Some(Diagnostic { file: file?, line: line?, message })
+}
+
+/// Render the snippet as a single multi-line string so it can slot into the
+/// existing message-log display without changing how messages are stored.
+pub fn render_snippet(source: &str, diagnostic: &Diagnostic) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let Some(idx) = diagnostic.line.checked_sub(1) else { return diagnostic.message.clone() };
+    if idx >= lines.len() {
+        return diagnostic.message.clone();
+    }
+
+    let start = idx.saturating_sub(1);
+    let end = (idx + 1).min(lines.len() - 1);
+    let gutter_width = (end + 1).to_string().len();
+
+    let mut out = String::new();
+    for line_no in start..=end {
+        let text = lines[line_no];
+        out.push_str(&format!("{:>width$} | {}\n", line_no + 1, text, width = gutter_width));
+
+        if line_no == idx {
+            let indent = " ".repeat(gutter_width + 3);
+            let caret_col = text.len() - text.trim_start().len();
+            let underline_len = text.trim().len().max(1);
+            out.push_str(&indent);
+            out.push_str(&" ".repeat(caret_col));
+            out.push_str(&"^".repeat(underline_len));
+            out.push('\n');
+            out.push_str(&indent);
+            out.push_str(&diagnostic.message);
+            out.push('\n');
+        }
+    }
+
+    out.pop(); // drop trailing newline
+    out
+}
@@ -3,89 +3,343 @@ use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
-use tree_sitter::{Parser, Query, QueryCursor};
+use tree_sitter::{InputEdit, Language, Parser, Point, Query, QueryCursor, Tree};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Chunk {
     pub source: String,
+    /// Full path to the file this chunk was extracted from, distinct from
+    /// `filename` (which is just the file stem). Combined with `func_name`
+    /// this is the chunk's stable identity: two definitions with the same
+    /// name in different files are different chunks, not the same one.
+    #[serde(default)]
+    pub path: String,
     pub filename: String,
     pub func_name: String,
+    /// Language tag from the grammar that produced this chunk (e.g. "python",
+    /// "rust"), used by the retriever and the assembler bridge to decide how
+    /// to treat the snippet.
+    #[serde(default)]
+    pub language: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-pub struct Index {
-    pub chunks: HashMap<String, Chunk>,
+impl Chunk {
+    /// Stable identity by file and name, used to key chunks, call-graph
+    /// entries, and embeddings so two same-named definitions in different
+    /// files never collapse into one. Same-named definitions within one
+    /// file share a key.
+    pub fn key(&self) -> String {
+        format!("{}::{}", self.path, self.func_name)
+    }
+}
+
+/// One file's worth of extracted definitions plus the call/import graph
+/// entries for those definitions, ready to be upserted into the `Store`.
+#[derive(Debug, Default)]
+pub struct ParsedFile {
+    pub chunks: Vec<Chunk>,
+    pub call_graph: HashMap<String, Vec<String>>,
 }
 
+/// A tree-sitter grammar plus the queries used to extract, per file,
+/// callable/type definitions and the identifiers those
+/// definitions reference (calls and imports), registered under a file
+/// extension. The definition patterns are not anchored to the file's root,
+/// so nested definitions (methods, inner functions) are extracted as well
+/// as top-level ones.
+struct Grammar {
+    language: Language,
+    query: Query,
+    /// Captures call targets and imported names scoped to a single
+    /// definition's subtree, feeding the dependency graph.
+    reference_query: Query,
+    /// Human-readable language tag stored on each `Chunk`.
+    name: &'static str,
+}
+
+/// Registry of tree-sitter grammars keyed by file extension, so the engine
+/// can index polyglot repos instead of assuming Python everywhere. Also
+/// holds the previous parse tree per indexed file path so a later change to
+/// that file can be re-parsed incrementally instead of from scratch.
 pub struct Indexer {
     parser: Parser,
-    query: Query,
+    grammars: HashMap<&'static str, Grammar>,
+    trees: HashMap<String, Tree>,
+    /// Last-seen source per indexed file path, kept alongside `trees` so a
+    /// later re-parse can diff against it to build a real edit range.
+    sources: HashMap<String, String>,
 }
 
 impl Indexer {
     pub fn new() -> Result<Self> {
-        let mut parser = Parser::new();
-        let language = tree_sitter_python::language();
-        parser.set_language(language)
-            .map_err(|e| anyhow::anyhow!("Failed to set language: {e}"))?;
+        let mut grammars = HashMap::new();
+
+        grammars.insert("py", Self::build_grammar(
+            tree_sitter_python::language(),
+            "python",
+            "(function_definition
+              name: (identifier) @name) @definition
+             (class_definition
+              name: (identifier) @name) @definition",
+            "(call
+              function: (identifier) @callee)
+             (call
+              function: (attribute attribute: (identifier) @callee))
+             (import_statement
+              name: (dotted_name (identifier) @import_name))
+             (import_from_statement
+              module_name: (dotted_name (identifier) @import_name))",
+        )?);
+
+        grammars.insert("js", Self::build_grammar(
+            tree_sitter_javascript::language(),
+            "javascript",
+            "(function_declaration
+              name: (identifier) @name) @definition
+             (class_declaration
+              name: (identifier) @name) @definition",
+            "(call_expression
+              function: (identifier) @callee)
+             (call_expression
+              function: (member_expression property: (property_identifier) @callee))
+             (import_specifier
+              name: (identifier) @import_name)",
+        )?);
+
+        grammars.insert("ts", Self::build_grammar(
+            tree_sitter_typescript::language_typescript(),
+            "typescript",
+            "(function_declaration
+              name: (identifier) @name) @definition
+             (class_declaration
+              name: (type_identifier) @name) @definition",
+            "(call_expression
+              function: (identifier) @callee)
+             (call_expression
+              function: (member_expression property: (property_identifier) @callee))
+             (import_specifier
+              name: (identifier) @import_name)",
+        )?);
 
-        let query_scm = "
-        (function_definition
-          name: (identifier) @name) @function
-        (class_definition
-          name: (identifier) @name) @class
-        ";
+        grammars.insert("rs", Self::build_grammar(
+            tree_sitter_rust::language(),
+            "rust",
+            "(function_item
+              name: (identifier) @name) @definition
+             (impl_item
+              type: (type_identifier) @name) @definition",
+            "(call_expression
+              function: (identifier) @callee)
+             (call_expression
+              function: (field_expression field: (field_identifier) @callee))
+             (use_declaration
+              argument: (identifier) @import_name)",
+        )?);
+
+        grammars.insert("go", Self::build_grammar(
+            tree_sitter_go::language(),
+            "go",
+            "(function_declaration
+              name: (identifier) @name) @definition
+             (type_declaration
+              (type_spec name: (type_identifier) @name)) @definition",
+            "(call_expression
+              function: (identifier) @callee)
+             (call_expression
+              function: (selector_expression field: (field_identifier) @callee))",
+        )?);
+
+        Ok(Self { parser: Parser::new(), grammars, trees: HashMap::new(), sources: HashMap::new() })
+    }
+
+    fn build_grammar(
+        language: Language,
+        name: &'static str,
+        query_scm: &str,
+        reference_query_scm: &str,
+    ) -> Result<Grammar> {
         let query = Query::new(language, query_scm)
-            .map_err(|e| anyhow::anyhow!("Failed to compile query: {e}"))?;
+            .map_err(|e| anyhow::anyhow!("Failed to compile {name} query: {e}"))?;
+        let reference_query = Query::new(language, reference_query_scm)
+            .map_err(|e| anyhow::anyhow!("Failed to compile {name} reference query: {e}"))?;
+        Ok(Grammar { language, query, reference_query, name })
+    }
 
-        Ok(Self { parser, query })
+    /// Whether `ext` (without the leading dot) has a registered grammar.
+    pub fn supports_extension(&self, ext: &str) -> bool {
+        self.grammars.contains_key(ext)
     }
 
-    pub fn parse_file(&mut self, path: &Path, index: &mut Index) -> Result<()> {
+    pub fn parse_file(&mut self, path: &Path) -> Result<ParsedFile> {
         let source_code = fs::read_to_string(path)
             .with_context(|| format!("Failed to read file: {path:?}"))?;
-        
+        let (tree, parsed) = self.extract(path, &source_code, None)?;
+        let key = path_key(path);
+        self.trees.insert(key.clone(), tree);
+        self.sources.insert(key, source_code);
+        Ok(parsed)
+    }
+
+    /// Re-parse a file that changed on disk, feeding tree-sitter the prior
+    /// tree so it only has to re-walk the edited ranges.
+    ///
+    /// The edit range is the common prefix/suffix between the previous
+    /// source and the new one: everything before the first differing byte
+    /// and after the last differing byte is assumed unchanged, and only the
+    /// byte span between them is reported as edited.
+    pub fn reparse_file(&mut self, path: &Path) -> Result<ParsedFile> {
+        let key = path_key(path);
+        let source_code = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {path:?}"))?;
+
+        if let (Some(old_tree), Some(old_source)) = (self.trees.get_mut(&key), self.sources.get(&key)) {
+            if let Some(edit) = diff_edit(old_source, &source_code) {
+                old_tree.edit(&edit);
+            }
+        }
+
+        let old_tree = self.trees.get(&key).cloned();
+        let (tree, parsed) = self.extract(path, &source_code, old_tree.as_ref())?;
+        self.trees.insert(key.clone(), tree);
+        self.sources.insert(key, source_code);
+        Ok(parsed)
+    }
+
+    /// Drop a deleted file's cached tree and source.
+    pub fn forget_file(&mut self, path: &Path) {
+        let key = path_key(path);
+        self.trees.remove(&key);
+        self.sources.remove(&key);
+    }
+
+    fn extract(
+        &mut self,
+        path: &Path,
+        source_code: &str,
+        old_tree: Option<&Tree>,
+    ) -> Result<(Tree, ParsedFile)> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let grammar = self.grammars.get(ext)
+            .ok_or_else(|| anyhow::anyhow!("No registered grammar for extension: .{ext}"))?;
+
         let filename = path.file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("unknown")
             .to_string();
 
-        let tree = self.parser.parse(&source_code, None)
+        self.parser.set_language(grammar.language)
+            .map_err(|e| anyhow::anyhow!("Failed to set language: {e}"))?;
+
+        let tree = self.parser.parse(source_code, old_tree)
             .ok_or_else(|| anyhow::anyhow!("Failed to parse code"))?;
 
         let mut cursor = QueryCursor::new();
         // tree-sitter 0.20 API usage
-        let matches = cursor.matches(&self.query, tree.root_node(), source_code.as_bytes());
+        let matches = cursor.matches(&grammar.query, tree.root_node(), source_code.as_bytes());
 
+        let mut parsed = ParsedFile::default();
         for m in matches {
-            // Find the @name capture and the @function/@class capture
+            // Find the @name capture and the @definition capture
             let mut func_name = String::new();
-            let mut node_byte_range = 0..0;
-            
+            let mut definition_node = None;
+
             for capture in m.captures {
-                let capture_name = &self.query.capture_names()[capture.index as usize];
+                let capture_name = &grammar.query.capture_names()[capture.index as usize];
                 if capture_name == "name" {
                     func_name = capture.node.utf8_text(source_code.as_bytes())?.to_string();
-                } else if capture_name == "function" || capture_name == "class" {
-                    node_byte_range = capture.node.byte_range();
+                } else if capture_name == "definition" {
+                    definition_node = Some(capture.node);
                 }
             }
 
-            if !func_name.is_empty() && node_byte_range.end > node_byte_range.start {
-                let chunk_source = &source_code[node_byte_range];
-                index.chunks.insert(func_name.clone(), Chunk {
-                    source: chunk_source.to_string(),
-                    filename: filename.clone(),
-                    func_name,
-                });
+            let Some(definition_node) = definition_node else { continue };
+            if func_name.is_empty() || definition_node.byte_range().is_empty() {
+                continue;
             }
+
+            let chunk_source = &source_code[definition_node.byte_range()];
+            parsed.chunks.push(Chunk {
+                source: chunk_source.to_string(),
+                path: path_key(path),
+                filename: filename.clone(),
+                func_name: func_name.clone(),
+                language: grammar.name.to_string(),
+            });
+
+            let references = Self::collect_references(grammar, definition_node, source_code)?;
+            parsed.call_graph.insert(func_name, references);
         }
-        Ok(())
+        Ok((tree, parsed))
     }
 
-    pub fn save_index(&self, index: &Index, path: &Path) -> Result<()> {
-        let file = fs::File::create(path)?;
-        serde_json::to_writer_pretty(file, &index.chunks)?;
-        Ok(())
+    /// Walk a single definition's subtree and collect the names it calls or
+    /// imports, for the dependency graph used by graph-expansion retrieval.
+    fn collect_references(grammar: &Grammar, definition_node: tree_sitter::Node, source_code: &str) -> Result<Vec<String>> {
+        let mut cursor = QueryCursor::new();
+        let matches = cursor.matches(&grammar.reference_query, definition_node, source_code.as_bytes());
+
+        let mut references = Vec::new();
+        for m in matches {
+            for capture in m.captures {
+                references.push(capture.node.utf8_text(source_code.as_bytes())?.to_string());
+            }
+        }
+        references.sort();
+        references.dedup();
+        Ok(references)
+    }
+}
+
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// Build the tree-sitter edit describing how `new_source` differs from
+/// `old_source`, bounding the edited range to the common prefix/suffix
+/// between them instead of always spanning the whole document. Returns
+/// `None` when the two are identical (nothing to edit).
+fn diff_edit(old_source: &str, new_source: &str) -> Option<InputEdit> {
+    let old_bytes = old_source.as_bytes();
+    let new_bytes = new_source.as_bytes();
+
+    let prefix_len = old_bytes.iter().zip(new_bytes)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let max_suffix = (old_bytes.len() - prefix_len).min(new_bytes.len() - prefix_len);
+    let suffix_len = old_bytes[prefix_len..].iter().rev()
+        .zip(new_bytes[prefix_len..].iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let start_byte = prefix_len;
+    let old_end_byte = old_bytes.len() - suffix_len;
+    let new_end_byte = new_bytes.len() - suffix_len;
+
+    if start_byte == old_end_byte && start_byte == new_end_byte {
+        return None;
+    }
+
+    Some(InputEdit {
+        start_byte,
+        old_end_byte,
+        new_end_byte,
+        start_position: point_at(old_source, start_byte),
+        old_end_position: point_at(old_source, old_end_byte),
+        new_end_position: point_at(new_source, new_end_byte),
+    })
+}
+
+/// Row/column of `byte_offset` within `source`, as tree-sitter's `Point`.
+fn point_at(source: &str, byte_offset: usize) -> Point {
+    let mut row = 0;
+    let mut last_newline = 0;
+    for (i, b) in source.bytes().enumerate().take(byte_offset) {
+        if b == b'\n' {
+            row += 1;
+            last_newline = i + 1;
+        }
     }
+    Point::new(row, byte_offset - last_newline)
 }
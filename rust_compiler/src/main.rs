@@ -1,6 +1,8 @@
 mod indexer;
 mod retriever;
 mod assembler;
+mod diagnostics;
+mod store;
 
 use anyhow::Result;
 use crossterm::{
@@ -15,11 +17,38 @@ use ratatui::{
     widgets::{Paragraph, Wrap},
     Frame, Terminal,
 };
-use std::{io, process::Command, time::Duration};
+use notify::{Event as FsEvent, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{io, process::Command, sync::mpsc, time::Duration};
 
-use indexer::{Indexer, Index};
-use retriever::Retriever;
+use indexer::Indexer;
+use retriever::{normalize, Retriever};
 use assembler::Assembler;
+use store::Store;
+
+/// Name of the SQLite database file the index is persisted to, relative to
+/// the current directory, replacing the old `inverted_index.json` blob.
+const INDEX_DB_PATH: &str = "assembly_engine_index.db";
+
+/// Canonical store key for `path`. The initial scan walks `.` with
+/// `walkdir` (which yields paths like `./foo.py`), while the `notify`
+/// watcher commonly reports paths for the same files in absolute form -
+/// without reconciling the two, every edit after startup would orphan the
+/// row the scan created and insert a duplicate instead of updating it.
+/// Canonicalizing both write paths to the same absolute form keeps them
+/// in agreement. Falls back to canonicalizing the parent directory (so a
+/// `Remove` event for a file that's already gone still resolves), and as
+/// a last resort to the path's lossy string form.
+fn canonical_key(path: &std::path::Path) -> String {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical.to_string_lossy().to_string();
+    }
+    if let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) {
+        if let Ok(canonical_parent) = parent.canonicalize() {
+            return canonical_parent.join(file_name).to_string_lossy().to_string();
+        }
+    }
+    path.to_string_lossy().to_string()
+}
 
 enum AppState {
     Input,
@@ -35,8 +64,11 @@ struct App {
     indexer: Indexer,
     retriever: Retriever,
     assembler: Assembler,
-    index_data: Index,
+    store: Store,
     current_code: Option<assembler::CompilerOutput>,
+    // Kept alive for the App's lifetime; dropping it stops the watch.
+    _watcher: Option<RecommendedWatcher>,
+    watch_rx: Option<mpsc::Receiver<FsEvent>>,
 }
 
 impl App {
@@ -51,10 +83,82 @@ impl App {
             indexer: Indexer::new()?,
             retriever: Retriever::new(),
             assembler: Assembler::new(),
-            index_data: Index::default(),
+            store: Store::open(std::path::Path::new(INDEX_DB_PATH))?,
             current_code: None,
+            _watcher: None,
+            watch_rx: None,
         })
     }
+
+    /// Start watching the current directory for file changes so the index
+    /// can stay live without a full rescan every query cycle.
+    fn start_watching(&mut self) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<FsEvent>| {
+            if let Ok(event) = res {
+                let _ = tx.send(event);
+            }
+        })?;
+        watcher.watch(std::path::Path::new("."), RecursiveMode::Recursive)?;
+        self._watcher = Some(watcher);
+        self.watch_rx = Some(rx);
+        Ok(())
+    }
+
+    /// Drain any pending file-watcher events and incrementally re-index the
+    /// affected files, patching just their chunks instead of a full rescan.
+    fn process_watch_events(&mut self) -> Result<()> {
+        let Some(rx) = &self.watch_rx else { return Ok(()) };
+        let events: Vec<FsEvent> = rx.try_iter().collect();
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut touched = false;
+
+        for event in events {
+            for path in &event.paths {
+                let path_str = path.to_string_lossy();
+                if path_str.contains("venv")
+                    || path_str.contains("/.git/")
+                    || path_str.ends_with(INDEX_DB_PATH) {
+                    continue;
+                }
+                let is_registered = path.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|ext| self.indexer.supports_extension(ext));
+                if !is_registered {
+                    continue;
+                }
+
+                let key = canonical_key(path);
+
+                match event.kind {
+                    EventKind::Remove(_) => {
+                        self.indexer.forget_file(path);
+                        self.store.remove_file(&key)?;
+                        touched = true;
+                        self.messages.push(("System".to_string(), format!("Reindexed (removed) {}", path.display())));
+                    }
+                    EventKind::Create(_) | EventKind::Modify(_) => {
+                        if let Ok(parsed) = self.indexer.reparse_file(path) {
+                            let source = std::fs::read_to_string(path).unwrap_or_default();
+                            let hash = store::content_hash(&source);
+                            self.store.replace_file(&key, &hash, &parsed.chunks, &parsed.call_graph)?;
+                            touched = true;
+                            self.messages.push(("System".to_string(), format!("Reindexed {}", path.display())));
+                        }
+                    }
+                    _ => continue,
+                }
+            }
+        }
+
+        if touched {
+            self.retriever.load_index(std::path::Path::new(INDEX_DB_PATH))?;
+        }
+        Ok(())
+    }
 }
 
 #[tokio::main]
@@ -73,29 +177,55 @@ async fn main() -> Result<()> {
     app.messages.push(("System".to_string(), "Indexing files...".to_string()));
     terminal.draw(|f| ui(f, &app))?;
     
-    // Scan files - skip venv and src directories (index only user libraries)
+    // Scan files - skip venv, this tool's own source, and hidden directories
     let mut count = 0;
     for entry in walkdir::WalkDir::new(".") {
         let entry = entry?;
         let path = entry.path();
         let path_str = path.to_string_lossy();
-        // Skip venv, src, rust_compiler, and hidden directories
-        if path.extension().is_some_and(|e| e == "py") 
-            && !path_str.contains("venv") 
-            && !path_str.contains("/src/")
+        // Skip venv, rust_compiler (this tool's own source), and hidden directories
+        let has_registered_ext = path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| app.indexer.supports_extension(ext));
+        if has_registered_ext
+            && !path_str.contains("venv")
             && !path_str.contains("rust_compiler")
             && !path_str.starts_with("./.") {
-            if let Ok(_) = app.indexer.parse_file(path, &mut app.index_data) {
+            let path_key = canonical_key(path);
+            let source = std::fs::read_to_string(path).unwrap_or_default();
+            let hash = store::content_hash(&source);
+            if app.store.file_hash(&path_key)?.as_deref() == Some(hash.as_str()) {
+                continue;
+            }
+            if let Ok(parsed) = app.indexer.parse_file(path) {
+                app.store.replace_file(&path_key, &hash, &parsed.chunks, &parsed.call_graph)?;
                 count += 1;
             }
         }
     }
-    
-    app.indexer.save_index(&app.index_data, std::path::Path::new("inverted_index.json"))?;
-    app.retriever.load_index(std::path::Path::new("inverted_index.json"))?;
-    
+
+    // Best-effort semantic pass: embed any chunk that doesn't have a vector yet.
+    // Without an embedding model the bridge still returns hashed
+    // bag-of-words vectors; if the bridge itself can't be run this just fails
+    // quietly and the retriever falls back to lexical search.
+    let unembedded = app.store.chunks_missing_embeddings()?;
+    if !unembedded.is_empty() {
+        if let Ok(vectors) = app.assembler.embed_chunks(unembedded.clone()) {
+            for (chunk, mut vector) in unembedded.into_iter().zip(vectors) {
+                normalize(&mut vector);
+                app.store.set_embedding(&chunk.path, &chunk.func_name, &vector)?;
+            }
+        }
+    }
+
+    app.retriever.load_index(std::path::Path::new(INDEX_DB_PATH))?;
+
     app.messages.push(("System".to_string(), format!("Indexed {count} files. Type a query and press Enter.")));
 
+    if let Err(e) = app.start_watching() {
+        app.messages.push(("System".to_string(), format!("File watcher unavailable: {e}")));
+    }
+
     // Run Loop
     let res = run_app(&mut terminal, app).await;
 
@@ -117,6 +247,7 @@ async fn main() -> Result<()> {
 
 async fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> Result<()> {
     loop {
+        app.process_watch_events()?;
         terminal.draw(|f| ui(f, &app))?;
 
         if event::poll(Duration::from_millis(50))? {
@@ -208,7 +339,16 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> Result
                                         app.messages.push(("Output".to_string(), stdout));
                                     }
                                     if has_stderr {
-                                        app.messages.push(("Error".to_string(), stderr));
+                                        // Only render a snippet when the innermost frame is
+                                        // actually in the generated file - a frame from a
+                                        // dependency chunk or the stdlib points at source we
+                                        // don't have, so the line number would land on the
+                                        // wrong file's text if we rendered it anyway.
+                                        let rendered = diagnostics::parse_traceback(&stderr)
+                                            .filter(|diag| diag.file == code_obj.filename || diag.file == full_path)
+                                            .map(|diag| diagnostics::render_snippet(&code_obj.code, &diag))
+                                            .unwrap_or_else(|| stderr.clone());
+                                        app.messages.push(("Error".to_string(), rendered));
                                     }
                                     if !has_stdout && !has_stderr {
                                         app.messages.push(("Output".to_string(), "(no output)".to_string()));
@@ -233,7 +373,13 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> Result
 }
 
 fn process_query(app: &mut App, query: String) -> Result<()> {
-    let chunks = app.retriever.search(&query);
+    let seeds = if app.retriever.has_embeddings() {
+        app.retriever.search_semantic(&query, &app.assembler)
+            .unwrap_or_else(|_| app.retriever.search(&query))
+    } else {
+        app.retriever.search(&query)
+    };
+    let chunks = app.retriever.expand_with_dependencies(seeds);
     app.messages.push(("System".to_string(), format!("Retrieved {} chunks.", chunks.len())));
     
     match app.assembler.generate_glue_code(chunks, query) {
@@ -1,49 +1,414 @@
-use anyhow::{Result, Context};
-use std::collections::HashMap;
-use std::fs;
+use anyhow::Result;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::path::Path;
-use crate::indexer::Chunk; // Assume Chunk is public in indexer
+use crate::assembler::Assembler;
+use crate::indexer::Chunk;
+use crate::store::Store;
+
+const TOP_K: usize = 5;
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+/// Depth of the breadth-first dependency expansion past the initial seeds.
+const MAX_EXPANSION_DEPTH: usize = 2;
+/// Hard cap on how many chunks graph-expansion will return, seeds included.
+const MAX_EXPANDED_CHUNKS: usize = 20;
+
+/// Wraps a scored chunk so it can live in a min-heap ordered by score, letting
+/// us keep only the top-k results without sorting the whole corpus.
+struct ScoredChunk {
+    score: f32,
+    chunk: Chunk,
+}
+
+impl PartialEq for ScoredChunk {
+    fn eq(&self, other: &Self) -> bool {
+        self.score == other.score
+    }
+}
+impl Eq for ScoredChunk {}
+
+impl PartialOrd for ScoredChunk {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoredChunk {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the heap is a min-heap on score: popping the smallest
+        // lets us evict it once we exceed `TOP_K` entries.
+        other.score.total_cmp(&self.score)
+    }
+}
+
+/// Precomputed BM25 statistics over the loaded corpus, kept alongside the
+/// index so scoring a query doesn't retokenize every chunk each time.
+#[derive(Default)]
+struct Bm25Stats {
+    /// `Chunk::key()` -> term -> term frequency within that chunk's source.
+    term_freqs: HashMap<String, HashMap<String, usize>>,
+    /// `Chunk::key()` -> document length (token count).
+    doc_lens: HashMap<String, usize>,
+    /// term -> number of chunks containing that term at least once.
+    doc_freqs: HashMap<String, usize>,
+    avgdl: f32,
+}
+
+impl Bm25Stats {
+    fn build(chunks: &HashMap<String, Chunk>) -> Self {
+        let mut stats = Bm25Stats::default();
+        let mut total_len = 0usize;
+
+        for chunk in chunks.values() {
+            let tokens = tokenize(&chunk.source);
+            let mut tf: HashMap<String, usize> = HashMap::new();
+            for token in &tokens {
+                *tf.entry(token.clone()).or_insert(0) += 1;
+            }
+            for term in tf.keys() {
+                *stats.doc_freqs.entry(term.clone()).or_insert(0) += 1;
+            }
+
+            total_len += tokens.len();
+            stats.doc_lens.insert(chunk.key(), tokens.len());
+            stats.term_freqs.insert(chunk.key(), tf);
+        }
+
+        stats.avgdl = if chunks.is_empty() {
+            0.0
+        } else {
+            total_len as f32 / chunks.len() as f32
+        };
+        stats
+    }
+
+    fn score(&self, chunk_key: &str, query_tokens: &[String]) -> f32 {
+        let n = self.doc_lens.len() as f32;
+        let Some(tf) = self.term_freqs.get(chunk_key) else { return 0.0 };
+        let doc_len = *self.doc_lens.get(chunk_key).unwrap_or(&0) as f32;
+
+        let mut score = 0.0;
+        for term in query_tokens {
+            let f = *tf.get(term).unwrap_or(&0) as f32;
+            if f == 0.0 {
+                continue;
+            }
+            let n_t = *self.doc_freqs.get(term).unwrap_or(&0) as f32;
+            let idf = ((n - n_t + 0.5) / (n_t + 0.5) + 1.0).ln();
+            let denom = f + BM25_K1 * (1.0 - BM25_B + BM25_B * doc_len / self.avgdl.max(1.0));
+            score += idf * (f * (BM25_K1 + 1.0)) / denom;
+        }
+        score
+    }
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            current.extend(c.to_lowercase());
+        } else if !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Index from a bare definition name to the keys of every chunk defining
+/// something with that name, across all files. Call-graph edges are just
+/// the identifier text a definition calls or imports - tree-sitter doesn't
+/// resolve them to a specific file - so matching them back to chunks is
+/// necessarily name-based and can fan out to more than one candidate.
+fn build_name_index(chunks: &HashMap<String, Chunk>) -> HashMap<String, Vec<String>> {
+    let mut index: HashMap<String, Vec<String>> = HashMap::new();
+    for chunk in chunks.values() {
+        index.entry(chunk.func_name.clone()).or_default().push(chunk.key());
+    }
+    index
+}
 
 pub struct Retriever {
-    index: HashMap<String, Chunk>,
+    store: Option<Store>,
+    /// Cached copy of the chunks table, keyed by `Chunk::key()`
+    /// (`path::func_name`), refreshed on `load_index`, so BM25 scoring and
+    /// graph expansion don't hit the database per query.
+    chunks: HashMap<String, Chunk>,
+    /// Cached copy of the call_graph table, keyed by the owning chunk's
+    /// `Chunk::key()`; values are bare referenced identifier names.
+    call_graph: HashMap<String, Vec<String>>,
+    /// Bare func_name -> chunk keys sharing that name, used to resolve
+    /// call-graph edges (which only have bare names) back to chunks.
+    by_func_name: HashMap<String, Vec<String>>,
+    bm25: Bm25Stats,
 }
 
 impl Retriever {
     pub fn new() -> Self {
-        Self { index: HashMap::new() }
+        Self {
+            store: None,
+            chunks: HashMap::new(),
+            call_graph: HashMap::new(),
+            by_func_name: HashMap::new(),
+            bm25: Bm25Stats::default(),
+        }
     }
 
+    /// Open the SQLite-backed index at `path` (creating it if missing) and
+    /// refresh the in-memory caches used for ranking from it.
     pub fn load_index(&mut self, path: &Path) -> Result<()> {
-        if !path.exists() {
-            // Return empty if no index
-            return Ok(());
-        }
-        let file = fs::File::open(path).with_context(|| "Failed to open index file")?;
-        self.index = serde_json::from_reader(file)?;
+        let store = Store::open(path)?;
+        self.chunks = store.all_chunks()?;
+        self.call_graph = store.call_graph()?;
+        self.by_func_name = build_name_index(&self.chunks);
+        self.bm25 = Bm25Stats::build(&self.chunks);
+        self.store = Some(store);
         Ok(())
     }
 
-    pub fn search(&self, query: &str) -> Vec<Chunk> {
-        let tokens: Vec<String> = query.split_whitespace()
-            .map(|s| s.to_lowercase())
-            .filter(|s| s.len() > 3)
-            .collect();
+    /// Ranks chunks by cosine similarity against an embedded query, joining
+    /// each candidate's vector from the embeddings table as it's scored.
+    /// Assumes chunk vectors were L2-normalized at index time, so cosine
+    /// similarity reduces to a plain dot product. Chunks with no stored
+    /// vector, or whose vector length doesn't match the query, are skipped.
+    pub fn search_semantic(&self, query: &str, assembler: &Assembler) -> Result<Vec<Chunk>> {
+        let Some(store) = &self.store else { return Ok(Vec::new()) };
+        let mut query_vec = assembler.embed_query(query)?;
+        normalize(&mut query_vec);
+
+        let mut heap: BinaryHeap<ScoredChunk> = BinaryHeap::new();
+
+        for chunk in self.chunks.values() {
+            let Some(embedding) = store.get_embedding(&chunk.path, &chunk.func_name)? else { continue };
+            if embedding.len() != query_vec.len() {
+                continue;
+            }
+
+            let score = dot(&embedding, &query_vec);
+            heap.push(ScoredChunk { score, chunk: chunk.clone() });
+            if heap.len() > TOP_K {
+                heap.pop();
+            }
+        }
+
+        let mut results: Vec<ScoredChunk> = heap.into_vec();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(results.into_iter().map(|r| r.chunk).collect())
+    }
+
+    /// Whether the loaded index has any embeddings at all. When it doesn't,
+    /// callers should fall back to `search` instead of `search_semantic`.
+    pub fn has_embeddings(&self) -> bool {
+        self.store.as_ref().and_then(|s| s.has_embeddings().ok()).unwrap_or(false)
+    }
 
-        if tokens.is_empty() {
+    /// BM25-ranked lexical search: scores every chunk against the query's
+    /// tokens and returns the top-k in descending score order. Chunks that
+    /// score zero (no query term present) are dropped.
+    pub fn search(&self, query: &str) -> Vec<Chunk> {
+        let query_tokens = tokenize(query);
+        if query_tokens.is_empty() {
             return Vec::new();
         }
 
-        let mut results = Vec::new();
+        let mut heap: BinaryHeap<ScoredChunk> = BinaryHeap::new();
+
+        for chunk in self.chunks.values() {
+            let score = self.bm25.score(&chunk.key(), &query_tokens);
+            if score <= 0.0 {
+                continue;
+            }
 
-        for chunk in self.index.values() {
-            let source_lower = chunk.source.to_lowercase();
-            let matches_any = tokens.iter().any(|t| source_lower.contains(t));
-            
-            if matches_any {
-                results.push(chunk.clone());
+            heap.push(ScoredChunk { score, chunk: chunk.clone() });
+            if heap.len() > TOP_K {
+                heap.pop();
             }
         }
-        
+
+        let mut results: Vec<ScoredChunk> = heap.into_vec();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results.into_iter().map(|r| r.chunk).collect()
+    }
+
+    /// Pull in the seed chunks' transitive dependencies via the call/import
+    /// graph, so the bridge gets self-contained context instead of just the
+    /// isolated matches. Bounded breadth-first: `MAX_EXPANSION_DEPTH` hops
+    /// out from the seeds, capped at `MAX_EXPANDED_CHUNKS` chunks total.
+    /// Seeds keep their original relevance order and come first; dependency
+    /// chunks are appended afterward in BFS discovery order. Dedup is by
+    /// `Chunk::key()`, so same-named definitions in different files are
+    /// treated as distinct nodes instead of collapsing into one.
+    pub fn expand_with_dependencies(&self, seeds: Vec<Chunk>) -> Vec<Chunk> {
+        let mut seen: HashSet<String> = seeds.iter().map(|c| c.key()).collect();
+        let mut results = seeds.clone();
+
+        let mut frontier: Vec<String> = seeds.iter()
+            .flat_map(|c| self.call_graph.get(&c.key()).cloned().unwrap_or_default())
+            .collect();
+
+        for _ in 0..MAX_EXPANSION_DEPTH {
+            if results.len() >= MAX_EXPANDED_CHUNKS || frontier.is_empty() {
+                break;
+            }
+
+            let mut next_frontier = Vec::new();
+            for name in frontier {
+                if results.len() >= MAX_EXPANDED_CHUNKS {
+                    break;
+                }
+                let Some(candidate_keys) = self.by_func_name.get(&name) else { continue };
+                for key in candidate_keys {
+                    if results.len() >= MAX_EXPANDED_CHUNKS {
+                        break;
+                    }
+                    if !seen.insert(key.clone()) {
+                        continue;
+                    }
+                    let Some(chunk) = self.chunks.get(key) else { continue };
+                    results.push(chunk.clone());
+                    if let Some(refs) = self.call_graph.get(key) {
+                        next_frontier.extend(refs.clone());
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+
         results
     }
 }
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// L2-normalize a vector in place, so cosine similarity between two
+/// normalized vectors reduces to a plain dot product. Shared by index-time
+/// embedding (main.rs) and query-time embedding (`search_semantic` above).
+pub fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for x in v.iter_mut() {
+            *x /= norm;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(path: &str, func_name: &str, source: &str) -> Chunk {
+        Chunk {
+            source: source.to_string(),
+            path: path.to_string(),
+            filename: "test".to_string(),
+            func_name: func_name.to_string(),
+            language: "python".to_string(),
+        }
+    }
+
+    fn retriever_with(chunks: HashMap<String, Chunk>, call_graph: HashMap<String, Vec<String>>) -> Retriever {
+        let by_func_name = build_name_index(&chunks);
+        Retriever {
+            store: None,
+            chunks,
+            call_graph,
+            by_func_name,
+            bm25: Bm25Stats::default(),
+        }
+    }
+
+    #[test]
+    fn bm25_ranks_more_relevant_chunk_first() {
+        let mut chunks = HashMap::new();
+        let heavy = chunk("a.py", "needle_heavy", "needle needle needle");
+        let light = chunk("b.py", "needle_light", "needle haystack haystack haystack haystack");
+        let unrelated = chunk("c.py", "unrelated", "haystack haystack haystack");
+        chunks.insert(heavy.key(), heavy.clone());
+        chunks.insert(light.key(), light.clone());
+        chunks.insert(unrelated.key(), unrelated.clone());
+
+        let bm25 = Bm25Stats::build(&chunks);
+        let tokens = tokenize("needle");
+
+        let heavy_score = bm25.score(&heavy.key(), &tokens);
+        let light_score = bm25.score(&light.key(), &tokens);
+        let unrelated_score = bm25.score(&unrelated.key(), &tokens);
+
+        assert!(heavy_score > light_score, "chunk with higher term frequency should score higher");
+        assert!(light_score > unrelated_score, "chunk containing the term should outscore one that doesn't");
+        assert_eq!(unrelated_score, 0.0);
+    }
+
+    #[test]
+    fn expand_with_dependencies_respects_depth_limit() {
+        let mut chunks = HashMap::new();
+        let mut call_graph = HashMap::new();
+        // seed -> a -> b -> c -> d (depth 4, but MAX_EXPANSION_DEPTH is 2)
+        let seed = chunk("seed.py", "seed", "seed");
+        chunks.insert(seed.key(), seed.clone());
+        for name in ["a", "b", "c", "d"] {
+            let c = chunk(&format!("{name}.py"), name, name);
+            chunks.insert(c.key(), c);
+        }
+        call_graph.insert(seed.key(), vec!["a".to_string()]);
+        call_graph.insert(chunk("a.py", "a", "a").key(), vec!["b".to_string()]);
+        call_graph.insert(chunk("b.py", "b", "b").key(), vec!["c".to_string()]);
+        call_graph.insert(chunk("c.py", "c", "c").key(), vec!["d".to_string()]);
+
+        let retriever = retriever_with(chunks, call_graph);
+
+        let results = retriever.expand_with_dependencies(vec![seed.clone()]);
+        let names: Vec<&str> = results.iter().map(|c| c.func_name.as_str()).collect();
+
+        assert_eq!(names[0], "seed", "seed must come first, preserving relevance order");
+        assert!(names.contains(&"a"), "depth-1 dependency should be included");
+        assert!(names.contains(&"b"), "depth-2 dependency should be included");
+        assert!(!names.contains(&"c"), "depth-3 dependency exceeds MAX_EXPANSION_DEPTH");
+        assert!(!names.contains(&"d"));
+    }
+
+    #[test]
+    fn expand_with_dependencies_caps_total_chunks() {
+        let mut chunks = HashMap::new();
+        let mut call_graph = HashMap::new();
+        let seed = chunk("seed.py", "seed", "seed");
+        chunks.insert(seed.key(), seed.clone());
+        let deps: Vec<String> = (0..MAX_EXPANDED_CHUNKS + 10).map(|i| format!("dep{i}")).collect();
+        for dep in &deps {
+            let c = chunk(&format!("{dep}.py"), dep, dep);
+            chunks.insert(c.key(), c);
+        }
+        call_graph.insert(seed.key(), deps);
+
+        let retriever = retriever_with(chunks, call_graph);
+
+        let results = retriever.expand_with_dependencies(vec![seed]);
+        assert!(results.len() <= MAX_EXPANDED_CHUNKS);
+    }
+
+    #[test]
+    fn expand_with_dependencies_keeps_same_named_chunks_from_different_files_distinct() {
+        let mut chunks = HashMap::new();
+        let mut call_graph = HashMap::new();
+        let seed = chunk("seed.py", "seed", "seed");
+        let main_py = chunk("a.py", "main", "def main(): pass");
+        let main_go = chunk("b.go", "main", "func main() {}");
+        chunks.insert(seed.key(), seed.clone());
+        chunks.insert(main_py.key(), main_py.clone());
+        chunks.insert(main_go.key(), main_go.clone());
+        call_graph.insert(seed.key(), vec!["main".to_string()]);
+
+        let retriever = retriever_with(chunks, call_graph);
+        let results = retriever.expand_with_dependencies(vec![seed]);
+
+        assert!(results.iter().any(|c| c.key() == main_py.key()));
+        assert!(results.iter().any(|c| c.key() == main_go.key()));
+        assert_eq!(results.len(), 3, "both same-named candidates should be kept distinct, not merged");
+    }
+}
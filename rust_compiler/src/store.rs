@@ -0,0 +1,303 @@
+//! SQLite-backed persistent index, replacing the single `inverted_index.json`
+//! blob that had to be fully read and rewritten on every run. Chunks and
+//! embedding vectors each get their own table, and files are upserted by
+//! content hash so unchanged files are skipped instead of reparsed.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use crate::indexer::Chunk;
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open index database: {path:?}"))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS files (
+                path TEXT PRIMARY KEY,
+                content_hash TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS chunks (
+                path TEXT NOT NULL,
+                func_name TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                language TEXT NOT NULL,
+                source TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                PRIMARY KEY (path, func_name)
+            );
+            CREATE TABLE IF NOT EXISTS call_graph (
+                path TEXT NOT NULL,
+                func_name TEXT NOT NULL,
+                reference TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS call_graph_by_owner ON call_graph(path, func_name);
+            CREATE TABLE IF NOT EXISTS embeddings (
+                path TEXT NOT NULL,
+                func_name TEXT NOT NULL,
+                vector BLOB NOT NULL,
+                PRIMARY KEY (path, func_name)
+            );",
+        )?;
+
+        Ok(Self { conn })
+    }
+
+    /// Content hash for a file as it was indexed last time, if any. Compared
+    /// against the current file's hash at startup so unchanged files can
+    /// skip re-parsing entirely.
+    pub fn file_hash(&self, path: &str) -> Result<Option<String>> {
+        self.conn
+            .query_row("SELECT content_hash FROM files WHERE path = ?1", params![path], |row| row.get(0))
+            .optional_context()
+    }
+
+    /// Replace everything derived from `path`: its chunks, their call-graph
+    /// entries, their embeddings, and the file's stored hash. Every table is
+    /// keyed by `(path, func_name)`, not `func_name` alone, so two files
+    /// that happen to define a same-named top-level function (common across
+    /// the registered languages) never share or clobber a row. Within one
+    /// file, a later same-named definition replaces the earlier chunk row.
+    pub fn replace_file(
+        &mut self,
+        path: &str,
+        content_hash: &str,
+        chunks: &[Chunk],
+        call_graph: &HashMap<String, Vec<String>>,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+
+        tx.execute("DELETE FROM call_graph WHERE path = ?1", params![path])?;
+        tx.execute("DELETE FROM embeddings WHERE path = ?1", params![path])?;
+        tx.execute("DELETE FROM chunks WHERE path = ?1", params![path])?;
+
+        for chunk in chunks {
+            tx.execute(
+                "INSERT OR REPLACE INTO chunks (path, func_name, filename, language, source, content_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![path, chunk.func_name, chunk.filename, chunk.language, chunk.source, content_hash],
+            )?;
+
+            if let Some(refs) = call_graph.get(&chunk.func_name) {
+                for reference in refs {
+                    tx.execute(
+                        "INSERT INTO call_graph (path, func_name, reference) VALUES (?1, ?2, ?3)",
+                        params![path, chunk.func_name, reference],
+                    )?;
+                }
+            }
+        }
+
+        tx.execute(
+            "INSERT INTO files (path, content_hash) VALUES (?1, ?2)
+             ON CONFLICT(path) DO UPDATE SET content_hash = excluded.content_hash",
+            params![path, content_hash],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Drop a deleted file's chunks, call-graph entries, embeddings, and
+    /// hash record.
+    pub fn remove_file(&mut self, path: &str) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM call_graph WHERE path = ?1", params![path])?;
+        tx.execute("DELETE FROM embeddings WHERE path = ?1", params![path])?;
+        tx.execute("DELETE FROM chunks WHERE path = ?1", params![path])?;
+        tx.execute("DELETE FROM files WHERE path = ?1", params![path])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// All indexed chunks, keyed by `Chunk::key()` (`path::func_name`) so
+    /// same-named definitions in different files each get their own entry
+    /// instead of collapsing into one.
+    pub fn all_chunks(&self) -> Result<HashMap<String, Chunk>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT path, func_name, filename, language, source FROM chunks",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Chunk {
+                path: row.get(0)?,
+                func_name: row.get(1)?,
+                filename: row.get(2)?,
+                language: row.get(3)?,
+                source: row.get(4)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<Chunk>>>()
+            .context("Failed to read chunks from index database")
+            .map(|chunks| chunks.into_iter().map(|c| (c.key(), c)).collect())
+    }
+
+    /// Call-graph edges, keyed by the owning chunk's `Chunk::key()`. Edge
+    /// values are the bare identifier text a definition calls or imports -
+    /// tree-sitter captures the call-site text, not a resolved target, so
+    /// matching them back to chunks is still name-based (see
+    /// `Retriever::expand_with_dependencies`).
+    pub fn call_graph(&self) -> Result<HashMap<String, Vec<String>>> {
+        let mut stmt = self.conn.prepare("SELECT path, func_name, reference FROM call_graph")?;
+        let rows = stmt.query_map([], |row| {
+            let path: String = row.get(0)?;
+            let func_name: String = row.get(1)?;
+            let reference: String = row.get(2)?;
+            Ok((format!("{path}::{func_name}"), reference))
+        })?;
+
+        let mut graph: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            let (owner_key, reference) = row?;
+            graph.entry(owner_key).or_default().push(reference);
+        }
+        Ok(graph)
+    }
+
+    pub fn chunks_missing_embeddings(&self) -> Result<Vec<Chunk>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.path, c.func_name, c.filename, c.language, c.source
+             FROM chunks c
+             LEFT JOIN embeddings e ON e.path = c.path AND e.func_name = c.func_name
+             WHERE e.func_name IS NULL",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Chunk {
+                path: row.get(0)?,
+                func_name: row.get(1)?,
+                filename: row.get(2)?,
+                language: row.get(3)?,
+                source: row.get(4)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<_>>().context("Failed to read unembedded chunks")
+    }
+
+    pub fn set_embedding(&self, path: &str, func_name: &str, vector: &[f32]) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO embeddings (path, func_name, vector) VALUES (?1, ?2, ?3)
+             ON CONFLICT(path, func_name) DO UPDATE SET vector = excluded.vector",
+            params![path, func_name, vector_to_blob(vector)],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_embedding(&self, path: &str, func_name: &str) -> Result<Option<Vec<f32>>> {
+        self.conn
+            .query_row(
+                "SELECT vector FROM embeddings WHERE path = ?1 AND func_name = ?2",
+                params![path, func_name],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional_context()
+            .map(|blob| blob.map(|b| blob_to_vector(&b)))
+    }
+
+    pub fn has_embeddings(&self) -> Result<bool> {
+        let count: i64 = self.conn.query_row("SELECT COUNT(*) FROM embeddings", [], |row| row.get(0))?;
+        Ok(count > 0)
+    }
+}
+
+/// Small extension trait so `rusqlite`'s "no rows" error reads as `Ok(None)`
+/// instead of needing a match at every call site.
+trait OptionalExt<T> {
+    fn optional_context(self) -> Result<Option<T>>;
+}
+
+impl<T> OptionalExt<T> for rusqlite::Result<T> {
+    fn optional_context(self) -> Result<Option<T>> {
+        match self {
+            Ok(v) => Ok(Some(v)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn vector_to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+fn blob_to_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+/// Change-detection hash for a file's contents. Not cryptographic - just
+/// needs to reliably flag "this file changed since the last index run".
+pub fn content_hash(source: &str) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    source.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(path: &str, func_name: &str, source: &str) -> Chunk {
+        Chunk {
+            source: source.to_string(),
+            path: path.to_string(),
+            filename: func_name.to_string(),
+            func_name: func_name.to_string(),
+            language: "python".to_string(),
+        }
+    }
+
+    #[test]
+    fn same_func_name_in_different_files_does_not_clobber() {
+        let mut store = Store::open(Path::new(":memory:")).unwrap();
+
+        store.replace_file("a.py", "hash_a", &[chunk("a.py", "main", "def main(): pass  # a")], &HashMap::new()).unwrap();
+        store.replace_file("b.go", "hash_b", &[chunk("b.go", "main", "func main() {} // b")], &HashMap::new()).unwrap();
+
+        // Both files' same-named chunks must survive independently through
+        // the path the app actually uses, keyed by (path, func_name).
+        let chunks = store.all_chunks().unwrap();
+        assert_eq!(chunks.len(), 2, "each file's same-named chunk should get its own entry");
+
+        let a = &chunks["a.py::main"];
+        let b = &chunks["b.go::main"];
+        assert!(a.source.contains("# a"));
+        assert!(b.source.contains("// b"));
+    }
+
+    #[test]
+    fn replace_file_overwrites_only_its_own_chunks() {
+        let mut store = Store::open(Path::new(":memory:")).unwrap();
+
+        store.replace_file("a.py", "hash_1", &[chunk("a.py", "foo", "v1")], &HashMap::new()).unwrap();
+        store.replace_file("a.py", "hash_2", &[chunk("a.py", "foo", "v2")], &HashMap::new()).unwrap();
+
+        let chunks = store.all_chunks().unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks["a.py::foo"].source, "v2");
+    }
+
+    #[test]
+    fn call_graph_entries_stay_scoped_per_owning_file() {
+        let mut store = Store::open(Path::new(":memory:")).unwrap();
+
+        let mut graph_a = HashMap::new();
+        graph_a.insert("main".to_string(), vec!["helper_a".to_string()]);
+        let mut graph_b = HashMap::new();
+        graph_b.insert("main".to_string(), vec!["helper_b".to_string()]);
+
+        store.replace_file("a.py", "hash_a", &[chunk("a.py", "main", "def main(): helper_a()")], &graph_a).unwrap();
+        store.replace_file("b.go", "hash_b", &[chunk("b.go", "main", "func main() { helper_b() }")], &graph_b).unwrap();
+
+        let graph = store.call_graph().unwrap();
+        assert_eq!(graph["a.py::main"], vec!["helper_a".to_string()]);
+        assert_eq!(graph["b.go::main"], vec!["helper_b".to_string()]);
+    }
+}